@@ -11,58 +11,169 @@ use anyhow::{Context, Result};
 
 mod account;
 mod amount;
+mod journal;
 mod transaction;
 
 pub use account::{Account, Accounts, ClientId, RawAccount};
 pub use amount::{deserialize_amount, serialize_amount, Amount};
-pub use transaction::{RawTransaction, Transaction, TransactionId, TransactionType};
+pub use journal::{verify, verify_all, Entry, Hash, Journal};
+pub use transaction::{
+    RawTransaction, Transaction, TransactionId, TransactionType, TxState, CURRENT_FORMAT_VERSION,
+};
+
+use crate::sink::OutputSink;
+
+/// Hash the genesis entry of every [`Journal`] chains from.
+fn genesis_hash() -> Hash {
+    blake3::hash(b"jeandudey/test transaction journal genesis")
+}
+
+/// Build a [`csv::ReaderBuilder`] configured for the transaction CSVs this
+/// processor consumes.
+///
+/// Real inputs pad fields with whitespace and omit trailing columns (e.g.
+/// the `amount` column on dispute/resolve/chargeback rows), so we trim all
+/// fields and allow records with a varying number of fields.
+pub fn configured_csv_reader_builder() -> csv::ReaderBuilder {
+    let mut builder = csv::ReaderBuilder::new();
+    builder
+        .has_headers(true)
+        .trim(csv::Trim::All)
+        .flexible(true);
+
+    builder
+}
 
 #[derive(Debug)]
 enum Action {
     /// A transaction to process.
     RawTx(RawTransaction),
-    /// Close the transaction processor, returns the state of the accounts.
-    Close(OneshotSender<Accounts>),
+    /// Take a snapshot of the journal without stopping the processor.
+    Snapshot(OneshotSender<Vec<Entry>>),
+    /// Close the transaction processor, returns the state of the accounts
+    /// together with the journal of every transaction applied.
+    Close(OneshotSender<(Accounts, Vec<Entry>)>),
 }
 
+/// The per-worker journal chains of a (possibly sharded) [`Task`]; see
+/// [`verify_all`] for why these are kept separate instead of merged into one
+/// chain.
+pub type Journals = Vec<Vec<Entry>>;
+
 /// Transaction processor task.
+///
+/// May be sharded across several workers (see [`Task::with_workers`]), each
+/// owning a disjoint [`Accounts`] and transaction map, plus its own sink, so
+/// unrelated clients' transactions (and, with `--postgres`, their sink I/O)
+/// proceed in parallel. A transaction is always routed to the same worker
+/// for a given client, so per-client ordering is preserved.
 #[derive(Debug)]
 pub struct Task {
-    sender: UnboundedSender<Action>,
+    senders: Vec<UnboundedSender<Action>>,
 }
 
 impl Task {
-    /// Spawn a new [`Task`] that will handle all the transactions.
-    pub fn new() -> Task {
-        let (sender, receiver) = unbounded_channel::<Action>();
-        // Spawn our transaction processor.
-        tokio::task::spawn(async move { task(receiver).await });
+    /// Spawn a new [`Task`] that will handle all the transactions on a
+    /// single worker, recording state into `sink` as it goes.
+    pub fn new(sink: Box<dyn OutputSink + Send>) -> Task {
+        Task::with_workers(vec![sink])
+    }
+
+    /// Spawn a new [`Task`] sharded across `sinks.len()` worker tasks,
+    /// partitioned by `ClientId % sinks.len()`. Each worker gets its own
+    /// sink (the `i`th worker uses `sinks[i]`) rather than sharing one, so
+    /// a `--postgres` sink's I/O parallelizes across workers instead of
+    /// serializing on a single connection.
+    pub fn with_workers(sinks: Vec<Box<dyn OutputSink + Send>>) -> Task {
+        assert!(!sinks.is_empty(), "a Task needs at least one worker");
 
-        Task { sender }
+        let senders = sinks
+            .into_iter()
+            .map(|sink| {
+                let (sender, receiver) = unbounded_channel::<Action>();
+                // Spawn our transaction processor.
+                tokio::task::spawn(task(receiver, sink));
+                sender
+            })
+            .collect();
+
+        Task { senders }
+    }
+
+    /// The worker a given client's transactions are routed to.
+    fn worker_for(&self, client: ClientId) -> &UnboundedSender<Action> {
+        &self.senders[client as usize % self.senders.len()]
     }
 
     /// Send a transaction to the [`Task`] to be processed.
+    ///
+    /// Every transaction for a given client is routed to the same worker,
+    /// so per-client ordering is preserved.
     pub fn send_tx(&self, tx: RawTransaction) -> Result<()> {
         Ok(self
-            .sender
+            .worker_for(tx.client)
             .send(Action::RawTx(tx))
             .context("Transaction processor task stopped")?)
     }
 
-    /// Close the [`Task`] and return the result of the operation in the accounts.
-    pub fn close(self) -> Result<Accounts> {
-        let (results_tx, mut results_rx) = oneshot_channel::<Accounts>();
-        self.sender
-            .send(Action::Close(results_tx))
-            .context("Transaction processor task stopped")?;
-
-        loop {
-            match results_rx.try_recv() {
-                Err(e) if e == TryRecvError::Empty => {}
-                Err(_) => anyhow::bail!("Could not retrieve acocunts information"),
-                Ok(accounts) => return Ok(accounts),
+    /// Take a snapshot of the journal of every transaction applied so far
+    /// across all workers, without stopping the processor. See [`Journals`]
+    /// for how to verify the result.
+    pub fn snapshot(&self) -> Result<Journals> {
+        let mut chains = Vec::new();
+
+        for sender in &self.senders {
+            let (results_tx, mut results_rx) = oneshot_channel::<Vec<Entry>>();
+            sender
+                .send(Action::Snapshot(results_tx))
+                .context("Transaction processor task stopped")?;
+
+            loop {
+                match results_rx.try_recv() {
+                    Err(e) if e == TryRecvError::Empty => {}
+                    Err(_) => anyhow::bail!("Could not retrieve journal snapshot"),
+                    Ok(worker_entries) => {
+                        chains.push(worker_entries);
+                        break;
+                    }
+                }
+            }
+        }
+
+        Ok(chains)
+    }
+
+    /// Close the [`Task`], fanning the close out to every worker, and
+    /// return the merged accounts together with every worker's journal
+    /// chain (see [`Journals`]).
+    ///
+    /// Account merging is a plain [`Extend::extend`]: each worker owns a
+    /// disjoint set of clients, so their [`Accounts`] never collide. Journal
+    /// chains are not merged the same way — they're kept separate.
+    pub fn close(self) -> Result<(Accounts, Journals)> {
+        let mut accounts = Accounts::new();
+        let mut chains = Vec::new();
+
+        for sender in &self.senders {
+            let (results_tx, mut results_rx) = oneshot_channel::<(Accounts, Vec<Entry>)>();
+            sender
+                .send(Action::Close(results_tx))
+                .context("Transaction processor task stopped")?;
+
+            loop {
+                match results_rx.try_recv() {
+                    Err(e) if e == TryRecvError::Empty => {}
+                    Err(_) => anyhow::bail!("Could not retrieve acocunts information"),
+                    Ok((worker_accounts, worker_entries)) => {
+                        accounts.data.extend(worker_accounts.data);
+                        chains.push(worker_entries);
+                        break;
+                    }
+                }
             }
         }
+
+        Ok((accounts, chains))
     }
 }
 
@@ -73,10 +184,10 @@ impl Task {
 /// convert it to a bounded receiver where we know approx. how much
 /// data we will have to sent to the thread. However for demonstration
 /// purposes an unbounded one works just fine.
-async fn task(mut actions: UnboundedReceiver<Action>) {
+async fn task(mut actions: UnboundedReceiver<Action>, mut sink: Box<dyn OutputSink + Send>) {
     // Keeo track of accounts and transactions. Accounts are created on deposits.
-    // Only deposit transactions are stored for now as they are only used here
-    // For the transaction types related to the dispues.
+    // Deposits and withdrawals are stored for posterior disputes, resolutions
+    // and chargebacks.
     //
     // Ideally the account data is saved to a database, but here we have to print it
     // to the console (in CSV) format, so it made sense to add a "Close" action that
@@ -84,9 +195,11 @@ async fn task(mut actions: UnboundedReceiver<Action>) {
     // return the accounts information to be printed.
     //
     // A hash map is used to reduce the lookup time of old transactions and the same
-    // is done with accounts.
+    // is done with accounts. Transactions are keyed by (client, tx) rather than just
+    // `tx` so a dispute can never reach into another client's transaction.
     let mut accounts = Accounts::new();
-    let mut transactions = HashMap::<TransactionId, Transaction>::new();
+    let mut transactions = HashMap::<(ClientId, TransactionId), Transaction>::new();
+    let mut journal = Journal::new(genesis_hash());
 
     // Process each action, they may come from anywhere here, the CSV is just
     // only one source :-D (intended to scale for multiple TCP streams sending
@@ -100,55 +213,111 @@ async fn task(mut actions: UnboundedReceiver<Action>) {
                 }
 
                 let account = accounts.get_or_create(raw_tx.client);
-                let tx: Transaction = raw_tx.into();
 
-                if tx.raw.tx_type == TransactionType::Deposit {
-                    // Only deposits and withdrawals contain IDs, however,
-                    // as this is only used to store transactions for posterior
-                    // disputes, reolutions and chargebacks there is no need to store
-                    // withdrawals as they are not disputed.
-                    if transactions.get(&tx.raw.id).is_none() {
-                        transactions.insert(tx.raw.id, tx.clone());
-                    }
+                // A locked account no longer accepts any operation.
+                if account.locked {
+                    continue;
+                }
+
+                // A row from a format_version newer than this build knows may
+                // carry trailing columns that change how it should be
+                // applied; no-op it rather than guessing, same as an
+                // unrecognized type.
+                if raw_tx.format_version > CURRENT_FORMAT_VERSION {
+                    eprintln!(
+                        "ignoring transaction {} for client {} with unsupported format_version {}",
+                        raw_tx.id, raw_tx.client, raw_tx.format_version
+                    );
+                    continue;
                 }
 
+                let tx: Transaction = raw_tx.into();
+                let key = (tx.raw.client, tx.raw.id);
+
                 // Process the transaction depending on it's type and apply the
-                // corresponding operation.
-                match tx.raw.tx_type {
-                    TransactionType::Deposit => account.deposit(tx.raw.amount),
-                    TransactionType::Withdrawal => account.withdraw(tx.raw.amount),
+                // corresponding operation. Deposits and withdrawals require an
+                // amount; disputes, resolves and chargebacks carry none and act
+                // on the amount of the transaction they reference instead, moving
+                // it through its `TxState` so invalid transitions are rejected.
+                //
+                // Deposits and withdrawals are only stored for later disputes
+                // (and only recorded in the journal/sink) once the account
+                // mutation actually succeeded, so a withdrawal rejected for
+                // insufficient funds never becomes a disputable history entry.
+                match &tx.raw.tx_type {
+                    TransactionType::Deposit => {
+                        if let Some(amount) = tx.raw.amount {
+                            account.deposit(amount);
+                            if transactions.get(&key).is_none() {
+                                transactions.insert(key, tx.clone());
+                            }
+                            journal.append(tx.raw.clone());
+                            sink.record_transaction(&tx).await.ok();
+                        }
+                    }
+                    TransactionType::Withdrawal => {
+                        if let Some(amount) = tx.raw.amount {
+                            if account.withdraw(amount) {
+                                if transactions.get(&key).is_none() {
+                                    transactions.insert(key, tx.clone());
+                                }
+                                journal.append(tx.raw.clone());
+                                sink.record_transaction(&tx).await.ok();
+                            }
+                        }
+                    }
                     TransactionType::Dispute => {
-                        // Find our disputed TX.
-                        if let Some(disputed_tx) = transactions.get_mut(&tx.raw.id) {
-                            account.hold(disputed_tx.raw.amount);
-                            disputed_tx.disputed = true;
+                        // Find our disputed TX, scoped to this client. Only
+                        // advance its state once the hold actually took
+                        // effect on the account, so a dispute that can't be
+                        // funded (e.g. the balance moved since the deposit)
+                        // never gets treated as disputed.
+                        if let Some(disputed_tx) = transactions.get_mut(&key) {
+                            let amount = disputed_tx.raw.amount.unwrap_or(Amount::ZERO);
+                            if disputed_tx.state == TxState::Processed && account.hold(amount) {
+                                disputed_tx.dispute();
+                                journal.append(tx.raw.clone());
+                                sink.record_transaction(disputed_tx).await.ok();
+                            }
                         }
                     }
                     TransactionType::Resolve => {
-                        if let Some(disputed_tx) = transactions.get_mut(&tx.raw.id) {
-                            // If not disputed, just ignore it.
-                            if !disputed_tx.disputed {
-                                continue;
+                        if let Some(disputed_tx) = transactions.get_mut(&key) {
+                            let amount = disputed_tx.raw.amount.unwrap_or(Amount::ZERO);
+                            if disputed_tx.state == TxState::Disputed && account.release(amount) {
+                                disputed_tx.resolve();
+                                journal.append(tx.raw.clone());
+                                sink.record_transaction(disputed_tx).await.ok();
                             }
-
-                            account.release(disputed_tx.raw.amount);
-                            disputed_tx.disputed = false;
                         }
                     }
                     TransactionType::Chargeback => {
-                        if let Some(disputed_tx) = transactions.get_mut(&tx.raw.id) {
-                            // If not disputed, just ignore it.
-                            if !disputed_tx.disputed {
-                                continue;
+                        if let Some(disputed_tx) = transactions.get_mut(&key) {
+                            let amount = disputed_tx.raw.amount.unwrap_or(Amount::ZERO);
+                            if disputed_tx.state == TxState::Disputed && account.chargeback(amount) {
+                                disputed_tx.chargeback();
+                                journal.append(tx.raw.clone());
+                                sink.record_transaction(disputed_tx).await.ok();
                             }
-
-                            account.chargeback(disputed_tx.raw.amount);
                         }
                     }
+                    TransactionType::Unknown(kind) => {
+                        // A newer producer's transaction type this build doesn't
+                        // know how to apply yet; no-op rather than drop the
+                        // whole stream.
+                        eprintln!(
+                            "ignoring transaction {} for client {} with unknown type {kind:?}",
+                            tx.raw.id, tx.raw.client
+                        );
+                    }
                 }
             }
+            Action::Snapshot(tx) => {
+                tx.send(journal.entries().to_vec()).ok();
+            }
             Action::Close(tx) => {
-                tx.send(accounts).ok();
+                sink.write_accounts(&accounts).await.ok();
+                tx.send((accounts, journal.entries().to_vec())).ok();
                 break;
             }
         }
@@ -37,39 +37,59 @@ impl Account {
     }
 
     /// Withdraw money from account.
-    pub fn withdraw(&mut self, amount: Amount) {
+    ///
+    /// Returns `false`, leaving the account untouched, if `amount` isn't
+    /// currently available.
+    pub fn withdraw(&mut self, amount: Amount) -> bool {
         // Withdraw only if the amount is available
         if amount > self.available {
-            return;
+            return false;
         }
 
         self.available -= amount;
+        true
     }
 
     /// Hold an amount of money from the acocunt.
-    pub fn hold(&mut self, amount: Amount) {
+    ///
+    /// Returns `false`, leaving the account untouched, if `amount` isn't
+    /// currently available.
+    pub fn hold(&mut self, amount: Amount) -> bool {
         if amount > self.available {
-            return;
+            return false;
         }
 
         self.available -= amount;
         self.held += amount;
+        true
     }
 
     /// Release an amount of held money from the account.
-    pub fn release(&mut self, amount: Amount) {
+    ///
+    /// Returns `false`, leaving the account untouched, if `amount` isn't
+    /// currently held.
+    pub fn release(&mut self, amount: Amount) -> bool {
         if amount > self.held {
-            return;
+            return false;
         }
 
         self.available += amount;
         self.held -= amount;
+        true
     }
 
     /// Take an amount of money held from the account and lock it.
-    pub fn chargeback(&mut self, amount: Amount) {
+    ///
+    /// Returns `false`, leaving the account untouched, if `amount` isn't
+    /// currently held.
+    pub fn chargeback(&mut self, amount: Amount) -> bool {
+        if amount > self.held {
+            return false;
+        }
+
         self.held -= amount;
         self.locked = true;
+        true
     }
 }
 
@@ -7,8 +7,12 @@ use std::{fmt, str::FromStr, string::ToString};
 /// only use 4 digits past the decimal.
 pub type Amount = fixed::types::I64F64;
 
-/// Custom deserializer function for an [`Amount`]
-pub fn deserialize_amount<'de, D>(deserializer: D) -> Result<Amount, D::Error>
+/// Custom deserializer function for an optional [`Amount`].
+///
+/// Dispute, resolve and chargeback rows carry no amount column, so an
+/// empty (or missing) value is treated as [`None`] instead of a parse
+/// error.
+pub fn deserialize_amount<'de, D>(deserializer: D) -> Result<Option<Amount>, D::Error>
 where
     D: serde::Deserializer<'de>
 {
@@ -17,17 +21,28 @@ where
     pub struct AmountVisitor;
 
     impl<'de> de::Visitor<'de> for AmountVisitor {
-        type Value = Amount;
+        type Value = Option<Amount>;
 
         fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-            formatter.write_str("a valid amount")
+            formatter.write_str("a valid amount or an empty string")
         }
 
         fn visit_str<E>(self, s: &str) -> Result<Self::Value, E>
         where
             E: de::Error,
         {
-            Amount::from_str(s).map_err(|e| de::Error::custom(e))
+            if s.is_empty() {
+                return Ok(None);
+            }
+
+            Amount::from_str(s).map(Some).map_err(|e| de::Error::custom(e))
+        }
+
+        fn visit_none<E>(self) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            Ok(None)
         }
     }
 
@@ -0,0 +1,212 @@
+use crate::core::RawTransaction;
+
+/// A blake3 digest, used to chain [`Entry`] records together.
+pub type Hash = blake3::Hash;
+
+/// A single entry in a [`Journal`].
+///
+/// `hash` is `H(prev_hash || seq || canonical_bytes(tx))`, so a consumer can
+/// recompute it from `prev_hash`, `seq` and `tx` alone; see [`verify`].
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub seq: u64,
+    pub prev_hash: Hash,
+    pub tx: RawTransaction,
+    pub hash: Hash,
+}
+
+/// Append-only, hash-chained log of every transaction applied by
+/// [`crate::core::Task`].
+///
+/// This makes a stream of transactions auditable: a consumer holding the
+/// journal can replay the exact order transactions were processed in, or
+/// use [`verify`] to cryptographically confirm the chain hasn't been
+/// tampered with, independent of the final account balances.
+#[derive(Debug, Clone)]
+pub struct Journal {
+    entries: Vec<Entry>,
+    prev_hash: Hash,
+    next_seq: u64,
+}
+
+impl Journal {
+    /// Create a new, empty [`Journal`] rooted at `genesis`.
+    pub fn new(genesis: Hash) -> Journal {
+        Journal {
+            entries: Vec::new(),
+            prev_hash: genesis,
+            next_seq: 0,
+        }
+    }
+
+    /// Append `tx` to the journal, chaining its hash onto the previous entry.
+    pub fn append(&mut self, tx: RawTransaction) {
+        let seq = self.next_seq;
+        let hash = entry_hash(self.prev_hash, seq, &tx);
+
+        self.entries.push(Entry {
+            seq,
+            prev_hash: self.prev_hash,
+            tx,
+            hash,
+        });
+
+        self.prev_hash = hash;
+        self.next_seq += 1;
+    }
+
+    /// The journal's entries, in the order they were appended.
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+}
+
+/// A stable, deterministic byte encoding of a [`RawTransaction`] used only
+/// for hashing: it must never change shape without also changing how
+/// existing journals verify.
+fn canonical_bytes(tx: &RawTransaction) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(1 + 2 + 2 + 1 + 16 + 1);
+
+    match &tx.tx_type {
+        crate::core::TransactionType::Deposit => bytes.push(0),
+        crate::core::TransactionType::Withdrawal => bytes.push(1),
+        crate::core::TransactionType::Dispute => bytes.push(2),
+        crate::core::TransactionType::Resolve => bytes.push(3),
+        crate::core::TransactionType::Chargeback => bytes.push(4),
+        crate::core::TransactionType::Unknown(kind) => {
+            bytes.push(5);
+            bytes.extend_from_slice(kind.as_bytes());
+        }
+    }
+
+    bytes.extend_from_slice(&tx.client.to_le_bytes());
+    bytes.extend_from_slice(&tx.id.to_le_bytes());
+
+    match tx.amount {
+        Some(amount) => {
+            bytes.push(1);
+            bytes.extend_from_slice(&amount.to_bits().to_le_bytes());
+        }
+        None => bytes.push(0),
+    }
+
+    bytes.push(tx.format_version);
+
+    bytes
+}
+
+fn entry_hash(prev_hash: Hash, seq: u64, tx: &RawTransaction) -> Hash {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(prev_hash.as_bytes());
+    hasher.update(&seq.to_le_bytes());
+    hasher.update(&canonical_bytes(tx));
+    hasher.finalize()
+}
+
+/// Recompute each entry's hash from the previous one, starting at `genesis`,
+/// and confirm the chain is unbroken.
+pub fn verify(entries: &[Entry], genesis: Hash) -> bool {
+    let mut prev_hash = genesis;
+
+    for entry in entries {
+        if entry.prev_hash != prev_hash || entry_hash(prev_hash, entry.seq, &entry.tx) != entry.hash {
+            return false;
+        }
+
+        prev_hash = entry.hash;
+    }
+
+    true
+}
+
+/// Verify several independently-rooted chains, such as the per-worker
+/// journals of a sharded [`crate::core::Task`].
+///
+/// Each chain in `chains` is a separate [`Journal`] that started from its own
+/// `genesis`; concatenating them into one [`Vec<Entry>`] and calling
+/// [`verify`] on that would fail as soon as a later chain's first entry
+/// (`seq = 0`, `prev_hash = genesis`) is reached. Verifying each chain on its
+/// own avoids that.
+pub fn verify_all(chains: &[Vec<Entry>], genesis: Hash) -> bool {
+    chains.iter().all(|chain| verify(chain, genesis))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::TransactionType;
+
+    fn genesis() -> Hash {
+        blake3::hash(b"journal test genesis")
+    }
+
+    fn deposit(client: u16, id: u16) -> RawTransaction {
+        RawTransaction {
+            tx_type: TransactionType::Deposit,
+            client,
+            id,
+            amount: Some(crate::core::Amount::ZERO),
+            format_version: crate::core::CURRENT_FORMAT_VERSION,
+        }
+    }
+
+    #[test]
+    fn verify_accepts_an_untampered_chain() {
+        let mut journal = Journal::new(genesis());
+        journal.append(deposit(1, 1));
+        journal.append(deposit(1, 2));
+
+        assert!(verify(journal.entries(), genesis()));
+    }
+
+    #[test]
+    fn verify_rejects_a_tampered_entry() {
+        let mut journal = Journal::new(genesis());
+        journal.append(deposit(1, 1));
+        journal.append(deposit(1, 2));
+
+        let mut entries = journal.entries().to_vec();
+        entries[0].tx.format_version = entries[0].tx.format_version.wrapping_add(1);
+
+        assert!(!verify(&entries, genesis()));
+    }
+
+    #[test]
+    fn verify_rejects_the_wrong_genesis() {
+        let mut journal = Journal::new(genesis());
+        journal.append(deposit(1, 1));
+
+        let other_genesis = blake3::hash(b"a different genesis");
+        assert!(!verify(journal.entries(), other_genesis));
+    }
+
+    #[test]
+    fn verify_all_checks_each_chain_independently() {
+        let mut worker_a = Journal::new(genesis());
+        worker_a.append(deposit(1, 1));
+
+        let mut worker_b = Journal::new(genesis());
+        worker_b.append(deposit(2, 1));
+
+        let chains = vec![worker_a.entries().to_vec(), worker_b.entries().to_vec()];
+        assert!(verify_all(&chains, genesis()));
+    }
+
+    #[test]
+    fn verify_all_rejects_a_chain_concatenated_as_one() {
+        let mut worker_a = Journal::new(genesis());
+        worker_a.append(deposit(1, 1));
+
+        let mut worker_b = Journal::new(genesis());
+        worker_b.append(deposit(2, 1));
+
+        // Flattening independently-rooted chains into one sequence breaks
+        // verification at the second chain's first entry: its `prev_hash`
+        // is `genesis`, but by then the accumulated `prev_hash` has already
+        // moved past it.
+        let mut flattened = worker_a.entries().to_vec();
+        flattened.extend(worker_b.entries().to_vec());
+
+        assert!(!verify(&flattened, genesis()));
+    }
+}
@@ -8,7 +8,7 @@ use crate::core::{Amount, ClientId};
 pub type TransactionId = u16;
 
 /// RawTransaction types.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum TransactionType {
     /// Deposit.
     Deposit,
@@ -20,6 +20,12 @@ pub enum TransactionType {
     Resolve,
     /// Chargeback.
     Chargeback,
+    /// A type string this build doesn't recognize.
+    ///
+    /// Keeps a stream mixing older and newer producers from failing to
+    /// deserialize altogether: rows of an unknown type are captured here
+    /// instead of the whole record being discarded.
+    Unknown(String),
 }
 
 impl<'de> Deserialize<'de> for TransactionType {
@@ -35,7 +41,7 @@ impl<'de> Deserialize<'de> for TransactionType {
             type Value = TransactionType;
 
             fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-                formatter.write_str("a valid transaction type string")
+                formatter.write_str("a transaction type string")
             }
 
             fn visit_str<E>(self, s: &str) -> Result<Self::Value, E>
@@ -48,7 +54,7 @@ impl<'de> Deserialize<'de> for TransactionType {
                     "dispute" => TransactionType::Dispute,
                     "resolve" => TransactionType::Resolve,
                     "chargeback" => TransactionType::Chargeback,
-                    _ => return Err(de::Error::invalid_value(de::Unexpected::Str(s), &self)),
+                    _ => TransactionType::Unknown(s.to_owned()),
                 })
             }
         }
@@ -72,22 +78,157 @@ pub struct RawTransaction {
     #[serde(rename = "tx")]
     pub id: TransactionId,
     /// Amount of the transaction.
-    #[serde(deserialize_with = "crate::core::deserialize_amount")]
-    pub amount: Amount,
+    ///
+    /// Only deposits and withdrawals carry an amount; dispute, resolve and
+    /// chargeback rows leave this column empty.
+    #[serde(default, deserialize_with = "crate::core::deserialize_amount")]
+    pub amount: Option<Amount>,
+    /// Format version of this row, selecting how any trailing columns a
+    /// future producer adds are to be interpreted.
+    ///
+    /// Absent in older inputs, which default to [`CURRENT_FORMAT_VERSION`]:
+    /// the plain `type,client,tx,amount` shape this module has always read.
+    /// Rows at a higher version may carry trailing columns this build
+    /// doesn't know how to interpret yet, so [`crate::core::Task`] no-ops
+    /// them rather than guessing.
+    #[serde(default = "default_format_version")]
+    pub format_version: u8,
+}
+
+/// The highest `format_version` this build knows how to interpret: just the
+/// `type,client,tx,amount` columns read by [`RawTransaction`].
+pub const CURRENT_FORMAT_VERSION: u8 = 1;
+
+fn default_format_version() -> u8 {
+    CURRENT_FORMAT_VERSION
+}
+
+/// State of a stored transaction in the dispute lifecycle.
+///
+/// A transaction starts out [`Processed`](TxState::Processed), can move to
+/// [`Disputed`](TxState::Disputed) and from there either back to
+/// `Processed` (resolved) or to the terminal
+/// [`ChargedBack`](TxState::ChargedBack) state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TxState {
+    /// Applied and not currently disputed.
+    Processed,
+    /// Disputed; its amount is held on the account.
+    Disputed,
+    /// Disputed and charged back. Terminal, no further transitions.
+    ChargedBack,
 }
 
 /// A transaction with information about it's state.
 #[derive(Debug, Clone)]
 pub struct Transaction {
     pub raw: RawTransaction,
-    pub disputed: bool,
+    pub state: TxState,
+}
+
+impl Transaction {
+    /// Move this transaction into the [`Disputed`](TxState::Disputed) state.
+    ///
+    /// Returns `false`, leaving the state untouched, if it isn't currently
+    /// [`Processed`](TxState::Processed).
+    pub fn dispute(&mut self) -> bool {
+        if self.state != TxState::Processed {
+            return false;
+        }
+
+        self.state = TxState::Disputed;
+        true
+    }
+
+    /// Resolve an active dispute, moving back to
+    /// [`Processed`](TxState::Processed).
+    ///
+    /// Returns `false`, leaving the state untouched, if it isn't currently
+    /// [`Disputed`](TxState::Disputed).
+    pub fn resolve(&mut self) -> bool {
+        if self.state != TxState::Disputed {
+            return false;
+        }
+
+        self.state = TxState::Processed;
+        true
+    }
+
+    /// Charge back an active dispute, moving to the terminal
+    /// [`ChargedBack`](TxState::ChargedBack) state.
+    ///
+    /// Returns `false`, leaving the state untouched, if it isn't currently
+    /// [`Disputed`](TxState::Disputed).
+    pub fn chargeback(&mut self) -> bool {
+        if self.state != TxState::Disputed {
+            return false;
+        }
+
+        self.state = TxState::ChargedBack;
+        true
+    }
 }
 
 impl From<RawTransaction> for Transaction {
     fn from(raw: RawTransaction) -> Self {
         Transaction {
             raw,
-            disputed: false,
+            state: TxState::Processed,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tx() -> Transaction {
+        RawTransaction {
+            tx_type: TransactionType::Deposit,
+            client: 1,
+            id: 1,
+            amount: Some(Amount::ZERO),
+            format_version: CURRENT_FORMAT_VERSION,
+        }
+        .into()
+    }
+
+    #[test]
+    fn dispute_then_resolve_returns_to_processed() {
+        let mut tx = tx();
+        assert!(tx.dispute());
+        assert_eq!(tx.state, TxState::Disputed);
+        assert!(tx.resolve());
+        assert_eq!(tx.state, TxState::Processed);
+    }
+
+    #[test]
+    fn dispute_then_chargeback_is_terminal() {
+        let mut tx = tx();
+        assert!(tx.dispute());
+        assert!(tx.chargeback());
+        assert_eq!(tx.state, TxState::ChargedBack);
+
+        // No further transitions out of ChargedBack.
+        assert!(!tx.dispute());
+        assert!(!tx.resolve());
+        assert!(!tx.chargeback());
+        assert_eq!(tx.state, TxState::ChargedBack);
+    }
+
+    #[test]
+    fn cannot_dispute_an_already_disputed_transaction() {
+        let mut tx = tx();
+        assert!(tx.dispute());
+        assert!(!tx.dispute());
+        assert_eq!(tx.state, TxState::Disputed);
+    }
+
+    #[test]
+    fn cannot_resolve_or_chargeback_without_a_prior_dispute() {
+        let mut tx = tx();
+        assert!(!tx.resolve());
+        assert!(!tx.chargeback());
+        assert_eq!(tx.state, TxState::Processed);
+    }
+}